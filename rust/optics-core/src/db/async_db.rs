@@ -0,0 +1,234 @@
+use std::future::Future;
+
+use ethers::types::H256;
+
+use crate::{accumulator::merkle::Proof, traits::RawCommittedMessage, SignedUpdate};
+
+use super::{DbError, DB};
+
+type Result<T> = std::result::Result<T, DbError>;
+
+/// An async wrapper around `DB`. `DB` is `Clone` (it's `Arc`-backed) but
+/// every one of its methods is a synchronous RocksDB call, so calling it
+/// directly from an agent's async tasks can stall unrelated futures on the
+/// same tokio worker for as long as a cold read or write takes. `AsyncDB`
+/// clones the inner `DB` and runs each call inside
+/// `tokio::task::spawn_blocking`, so the blocking work runs on tokio's
+/// blocking thread pool instead of a worker thread.
+#[derive(Debug, Clone)]
+pub struct AsyncDB(DB);
+
+impl From<DB> for AsyncDB {
+    fn from(db: DB) -> Self {
+        Self(db)
+    }
+}
+
+/// Clones `db` and runs `f` against the clone inside `spawn_blocking`.
+///
+/// Panics if the blocking task itself panics, rather than swallowing it,
+/// since a panic partway through a DB call indicates a broken invariant
+/// that callers shouldn't silently treat as a normal error.
+fn spawn_blocking_db<T, F>(db: &DB, f: F) -> impl Future<Output = Result<T>>
+where
+    T: Send + 'static,
+    F: FnOnce(&DB) -> Result<T> + Send + 'static,
+{
+    let db = db.clone();
+    async move {
+        tokio::task::spawn_blocking(move || f(&db))
+            .await
+            .expect("db blocking task panicked")
+    }
+}
+
+impl AsyncDB {
+    /// See `DB::store_raw_committed_message`
+    pub fn store_raw_committed_message(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        message: RawCommittedMessage,
+    ) -> impl Future<Output = Result<()>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.store_raw_committed_message(home_name, &message)
+        })
+    }
+
+    /// See `DB::update_latest_leaf_index`
+    pub fn update_latest_leaf_index(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        leaf_index: u32,
+    ) -> impl Future<Output = Result<()>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.update_latest_leaf_index(home_name, leaf_index)
+        })
+    }
+
+    /// See `DB::retrieve_latest_leaf_index`
+    pub fn retrieve_latest_leaf_index(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+    ) -> impl Future<Output = Result<Option<u32>>> {
+        spawn_blocking_db(&self.0, move |db| db.retrieve_latest_leaf_index(home_name))
+    }
+
+    /// See `DB::store_leaf`
+    pub fn store_leaf(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        leaf_index: u32,
+        destination_and_nonce: u64,
+        leaf_hash: H256,
+    ) -> impl Future<Output = Result<()>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.store_leaf(home_name, leaf_index, destination_and_nonce, leaf_hash)
+        })
+    }
+
+    /// See `DB::message_by_leaf_hash`
+    pub fn message_by_leaf_hash(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        leaf_hash: H256,
+    ) -> impl Future<Output = Result<Option<RawCommittedMessage>>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.message_by_leaf_hash(home_name, leaf_hash)
+        })
+    }
+
+    /// See `DB::leaf_by_leaf_index`
+    pub fn leaf_by_leaf_index(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        leaf_index: u32,
+    ) -> impl Future<Output = Result<Option<H256>>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.leaf_by_leaf_index(home_name, leaf_index)
+        })
+    }
+
+    /// See `DB::leaf_by_nonce`
+    pub fn leaf_by_nonce(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        destination: u32,
+        nonce: u32,
+    ) -> impl Future<Output = Result<Option<H256>>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.leaf_by_nonce(home_name, destination, nonce)
+        })
+    }
+
+    /// See `DB::message_by_nonce`
+    pub fn message_by_nonce(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        destination: u32,
+        nonce: u32,
+    ) -> impl Future<Output = Result<Option<RawCommittedMessage>>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.message_by_nonce(home_name, destination, nonce)
+        })
+    }
+
+    /// See `DB::message_by_leaf_index`
+    pub fn message_by_leaf_index(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        index: u32,
+    ) -> impl Future<Output = Result<Option<RawCommittedMessage>>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.message_by_leaf_index(home_name, index)
+        })
+    }
+
+    /// See `DB::retrieve_latest_root`
+    pub fn retrieve_latest_root(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+    ) -> impl Future<Output = Result<Option<H256>>> {
+        spawn_blocking_db(&self.0, move |db| db.retrieve_latest_root(home_name))
+    }
+
+    /// See `DB::store_update`
+    pub fn store_update(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        update: SignedUpdate,
+    ) -> impl Future<Output = Result<()>> {
+        spawn_blocking_db(&self.0, move |db| db.store_update(home_name, &update))
+    }
+
+    /// See `DB::update_by_previous_root`
+    pub fn update_by_previous_root(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        previous_root: H256,
+    ) -> impl Future<Output = Result<Option<SignedUpdate>>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.update_by_previous_root(home_name, previous_root)
+        })
+    }
+
+    /// See `DB::update_by_new_root`
+    pub fn update_by_new_root(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        new_root: H256,
+    ) -> impl Future<Output = Result<Option<SignedUpdate>>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.update_by_new_root(home_name, new_root)
+        })
+    }
+
+    /// See `DB::store_proof`
+    pub fn store_proof(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        leaf_index: u32,
+        proof: Proof,
+    ) -> impl Future<Output = Result<()>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.store_proof(home_name, leaf_index, &proof)
+        })
+    }
+
+    /// See `DB::proof_by_leaf_index`
+    pub fn proof_by_leaf_index(
+        &self,
+        home_name: impl AsRef<[u8]> + Send + 'static,
+        leaf_index: u32,
+    ) -> impl Future<Output = Result<Option<Proof>>> {
+        spawn_blocking_db(&self.0, move |db| {
+            db.proof_by_leaf_index(home_name, leaf_index)
+        })
+    }
+
+    /// See `DB::wait_for_leaf`. `DB::wait_for_leaf` itself calls the
+    /// synchronous `leaf_by_leaf_index` directly in its loop, so this
+    /// re-implements the same check-then-wait loop with the per-iteration
+    /// read run through `spawn_blocking` instead of delegating to it.
+    pub fn wait_for_leaf(
+        &self,
+        home_name: impl AsRef<[u8]> + Clone + Send + 'static,
+        leaf_index: u32,
+    ) -> impl Future<Output = Result<Option<H256>>> {
+        let db = self.0.clone();
+        async move {
+            loop {
+                let notify = db.leaf_notify(&home_name);
+                let notified = notify.notified();
+                let checked_home_name = home_name.clone();
+                if let Some(leaf) = spawn_blocking_db(&db, move |db| {
+                    db.leaf_by_leaf_index(checked_home_name, leaf_index)
+                })
+                .await?
+                {
+                    return Ok(Some(leaf));
+                }
+                notified.await;
+            }
+        }
+    }
+}
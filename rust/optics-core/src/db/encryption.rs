@@ -0,0 +1,76 @@
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use rand::RngCore;
+
+use super::DbError;
+
+const NONCE_LEN: usize = 12;
+
+/// A 32-byte symmetric key used to encrypt values at rest.
+///
+/// Each value is stored as `nonce || ciphertext`, with a fresh random nonce
+/// per write and the full prefixed key authenticated as associated data, so
+/// a ciphertext can't be copied or relocated onto a different key.
+#[derive(Clone)]
+pub struct EncryptionKey(Key);
+
+impl EncryptionKey {
+    /// Build an encryption key from 32 raw bytes
+    pub fn new(bytes: [u8; 32]) -> Self {
+        Self(*Key::from_slice(&bytes))
+    }
+
+    /// Encrypt `plaintext`, authenticating `aad`. Returns `nonce || ciphertext`.
+    pub fn encrypt(&self, aad: &[u8], plaintext: &[u8]) -> Vec<u8> {
+        let cipher = ChaCha20Poly1305::new(&self.0);
+
+        let mut nonce_bytes = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce_bytes);
+        let nonce = Nonce::from_slice(&nonce_bytes);
+
+        let ciphertext = cipher
+            .encrypt(
+                nonce,
+                Payload {
+                    msg: plaintext,
+                    aad,
+                },
+            )
+            .expect("chacha20poly1305 encryption failure");
+
+        let mut envelope = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        envelope.extend_from_slice(&nonce_bytes);
+        envelope.extend(ciphertext);
+        envelope
+    }
+
+    /// Decrypt a `nonce || ciphertext` envelope, authenticating `aad`.
+    /// Fails if the envelope is truncated, `aad` doesn't match what it was
+    /// encrypted with, or the key is wrong.
+    pub fn decrypt(&self, aad: &[u8], envelope: &[u8]) -> Result<Vec<u8>, DbError> {
+        if envelope.len() < NONCE_LEN {
+            return Err(DbError::DecryptionError);
+        }
+        let (nonce_bytes, ciphertext) = envelope.split_at(NONCE_LEN);
+        let nonce = Nonce::from_slice(nonce_bytes);
+        let cipher = ChaCha20Poly1305::new(&self.0);
+
+        cipher
+            .decrypt(
+                nonce,
+                Payload {
+                    msg: ciphertext,
+                    aad,
+                },
+            )
+            .map_err(|_| DbError::DecryptionError)
+    }
+}
+
+impl std::fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
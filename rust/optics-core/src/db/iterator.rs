@@ -0,0 +1,35 @@
+use std::marker::PhantomData;
+
+use crate::Decode;
+
+/// An iterator over every entry in a single column family, decoding each
+/// value as `T`. Column families already separate record types, so unlike
+/// the old prefix-matching iterator this only needs to skip entries whose
+/// value fails to decode.
+pub struct CfIterator<'a, T> {
+    iter: Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>,
+    _decodes_to: PhantomData<T>,
+}
+
+impl<'a, T> CfIterator<'a, T> {
+    /// Create a new `CfIterator` from a raw key/value iterator over a CF
+    pub fn new(iter: impl Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a) -> Self {
+        Self {
+            iter: Box::new(iter),
+            _decodes_to: Default::default(),
+        }
+    }
+}
+
+impl<'a, T: Decode> Iterator for CfIterator<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (_key, value) = self.iter.next()?;
+            if let Ok(item) = T::read_from(&mut value.as_ref()) {
+                return Some(item);
+            }
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+};
+
+use rocksdb::{ColumnFamily, DB as Rocks};
+
+use super::DbError;
+
+type Result<T> = std::result::Result<T, DbError>;
+
+/// A set of column-family-scoped key/value writes to commit atomically.
+/// Backend-agnostic so callers can build up a batch of writes without
+/// depending on `rocksdb::WriteBatch` directly.
+#[derive(Default)]
+pub struct Batch {
+    puts: Vec<(&'static str, Vec<u8>, Vec<u8>)>,
+    deletes: Vec<(&'static str, Vec<u8>)>,
+}
+
+impl Batch {
+    /// Queue a put onto this batch, in column family `cf`
+    pub fn put(&mut self, cf: &'static str, key: impl Into<Vec<u8>>, value: impl Into<Vec<u8>>) {
+        self.puts.push((cf, key.into(), value.into()));
+    }
+
+    /// Queue a delete onto this batch, in column family `cf`. Used by the
+    /// legacy-key migration to remove an entry from the default CF once it's
+    /// been rewritten into its proper one.
+    pub fn delete(&mut self, cf: &'static str, key: impl Into<Vec<u8>>) {
+        self.deletes.push((cf, key.into()));
+    }
+}
+
+/// A low-level, backend-agnostic key/value store, organized into named
+/// column families (one per Optics record type). `DB` is generic over this
+/// so the Optics-specific storage methods (messages, proofs, updates) don't
+/// need to know which engine is underneath, letting operators swap in an
+/// in-memory store for tests or a different embedded engine entirely.
+pub trait KvStore: Clone + Send + Sync {
+    /// Store a value at `key` in column family `cf`
+    fn kv_put(&self, cf: &str, key: &[u8], value: &[u8]) -> Result<()>;
+    /// Retrieve the value at `key` in column family `cf`, if any
+    fn kv_get(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>>;
+    /// Commit a batch of writes atomically
+    fn kv_write_batch(&self, batch: Batch) -> Result<()>;
+    /// Iterate over every key/value pair in column family `cf`. Errors if
+    /// `cf` isn't open on this store, rather than returning an iterator that
+    /// silently yields nothing.
+    fn kv_iter<'a>(
+        &'a self,
+        cf: &str,
+    ) -> Result<Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>>;
+}
+
+/// The default `KvStore`, backed by RocksDB column families
+#[derive(Debug, Clone)]
+pub struct RocksKv(Arc<Rocks>);
+
+impl From<Rocks> for RocksKv {
+    fn from(rocks: Rocks) -> Self {
+        Self(Arc::new(rocks))
+    }
+}
+
+impl From<Arc<Rocks>> for RocksKv {
+    fn from(rocks: Arc<Rocks>) -> Self {
+        Self(rocks)
+    }
+}
+
+impl RocksKv {
+    fn cf_handle(&self, cf: &str) -> Result<&ColumnFamily> {
+        self.0
+            .cf_handle(cf)
+            .ok_or_else(|| DbError::MissingColumnFamily(cf.to_owned()))
+    }
+}
+
+impl KvStore for RocksKv {
+    fn kv_put(&self, cf: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        let handle = self.cf_handle(cf)?;
+        Ok(self.0.put_cf(handle, key, value)?)
+    }
+
+    fn kv_get(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        let handle = self.cf_handle(cf)?;
+        Ok(self.0.get_cf(handle, key)?)
+    }
+
+    fn kv_write_batch(&self, batch: Batch) -> Result<()> {
+        let mut wb = rocksdb::WriteBatch::default();
+        for (cf, key, value) in batch.puts {
+            let handle = self.cf_handle(cf)?;
+            wb.put_cf(handle, key, value);
+        }
+        for (cf, key) in batch.deletes {
+            let handle = self.cf_handle(cf)?;
+            wb.delete_cf(handle, key);
+        }
+        Ok(self.0.write(wb)?)
+    }
+
+    fn kv_iter<'a>(
+        &'a self,
+        cf: &str,
+    ) -> Result<Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>> {
+        let handle = self.cf_handle(cf)?;
+        Ok(Box::new(self.0.iterator_cf(handle, rocksdb::IteratorMode::Start)))
+    }
+}
+
+/// An in-memory `KvStore`. Useful for deterministic unit tests of
+/// prover-sync and processor paths without spinning up a real RocksDB
+/// directory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryKv(Arc<Mutex<HashMap<String, BTreeMap<Vec<u8>, Vec<u8>>>>>);
+
+impl KvStore for MemoryKv {
+    fn kv_put(&self, cf: &str, key: &[u8], value: &[u8]) -> Result<()> {
+        self.0
+            .lock()
+            .expect("memory kv lock poisoned")
+            .entry(cf.to_owned())
+            .or_default()
+            .insert(key.to_vec(), value.to_vec());
+        Ok(())
+    }
+
+    fn kv_get(&self, cf: &str, key: &[u8]) -> Result<Option<Vec<u8>>> {
+        Ok(self
+            .0
+            .lock()
+            .expect("memory kv lock poisoned")
+            .get(cf)
+            .and_then(|col| col.get(key))
+            .cloned())
+    }
+
+    fn kv_write_batch(&self, batch: Batch) -> Result<()> {
+        let mut map = self.0.lock().expect("memory kv lock poisoned");
+        for (cf, key, value) in batch.puts {
+            map.entry(cf.to_owned()).or_default().insert(key, value);
+        }
+        for (cf, key) in batch.deletes {
+            if let Some(col) = map.get_mut(cf) {
+                col.remove(&key);
+            }
+        }
+        Ok(())
+    }
+
+    fn kv_iter<'a>(
+        &'a self,
+        cf: &str,
+    ) -> Result<Box<dyn Iterator<Item = (Box<[u8]>, Box<[u8]>)> + 'a>> {
+        let map = self.0.lock().expect("memory kv lock poisoned");
+        let entries: Vec<_> = map
+            .get(cf)
+            .into_iter()
+            .flatten()
+            .map(|(k, v)| (k.clone().into_boxed_slice(), v.clone().into_boxed_slice()))
+            .collect();
+        Ok(Box::new(entries.into_iter()))
+    }
+}
@@ -1,39 +1,103 @@
 use color_eyre::eyre::WrapErr;
 use ethers::types::H256;
-use rocksdb::{Options, DB as Rocks};
-use std::{future::Future, path::Path, sync::Arc, time::Duration};
-use tokio::time::sleep;
+use rocksdb::{ColumnFamilyDescriptor, Options, DB as Rocks};
+use std::{
+    collections::HashMap,
+    future::Future,
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use tokio::sync::Notify;
 use tracing::{debug, info};
 
+/// An async `DB` wrapper that runs calls on the blocking thread pool
+pub mod async_db;
+
+/// Optional at-rest value encryption
+pub mod encryption;
+
 /// Shared functionality surrounding use of rocksdb
 pub mod iterator;
 
+/// The backend-agnostic key/value store abstraction `DB` is generic over
+pub mod kv;
+
 use crate::{
     accumulator::merkle::Proof, traits::RawCommittedMessage, utils, Decode, Encode, OpticsError,
     OpticsMessage, SignedUpdate,
 };
 
-use self::iterator::PrefixIterator;
-
-// Type prefixes
-static NONCE: &str = "_destination_and_nonce_";
-static LEAF_IDX: &str = "_leaf_index_";
-static LEAF_HASH: &str = "_leaf_hash_";
-static PREV_ROOT: &str = "_update_prev_root_";
-static NEW_ROOT: &str = "_update_new_root_";
-static LATEST_ROOT: &str = "_update_latest_root_";
-static PROOF: &str = "_proof_";
-static LATEST_LEAF: &str = "_latest_known_leaf_";
+use self::{
+    encryption::EncryptionKey,
+    iterator::CfIterator,
+    kv::{Batch, KvStore, RocksKv},
+};
 
-/// A KV Store
+// Column families, one per Optics record type. Each holds the same
+// `<home_name><key>` byte keys that used to carry a string type-prefix in a
+// single shared keyspace; the CF now provides that separation instead.
+const NONCE: &str = "nonce";
+const LEAF_IDX: &str = "leaf_index";
+const LEAF_HASH: &str = "leaf_hash";
+const PREV_ROOT: &str = "prev_root";
+const NEW_ROOT: &str = "new_root";
+const LATEST_ROOT: &str = "latest_root";
+const PROOF: &str = "proof";
+const LATEST_LEAF: &str = "latest_leaf";
+
+const RECORD_COLUMN_FAMILIES: &[&str] = &[
+    NONCE,
+    LEAF_IDX,
+    LEAF_HASH,
+    PREV_ROOT,
+    NEW_ROOT,
+    LATEST_ROOT,
+    PROOF,
+    LATEST_LEAF,
+];
+
+// Pre-CF versions of this DB kept every record in rocksdb's default column
+// family, distinguished only by concatenating one of these type-prefix
+// strings into the key: `<home_name><type_prefix><key>`. Used only by
+// `migrate_legacy_keys` to recognize and rewrite keys left over from that
+// layout; new writes never use them.
+const LEGACY_PREFIXES: &[(&str, &str)] = &[
+    ("_destination_and_nonce_", NONCE),
+    ("_leaf_index_", LEAF_IDX),
+    ("_leaf_hash_", LEAF_HASH),
+    ("_update_prev_root_", PREV_ROOT),
+    ("_update_new_root_", NEW_ROOT),
+    ("_update_latest_root_", LATEST_ROOT),
+    ("_proof_", PROOF),
+    ("_latest_known_leaf_", LATEST_LEAF),
+];
+
+/// A KV Store, generic over the underlying storage engine
 ///
-/// Key structure: ```<home_name>_<type_prefix>_<key>```
+/// Key structure (within a record's column family): ```<home_name><key>```
 #[derive(Debug, Clone)]
-pub struct DB(Arc<Rocks>);
+pub struct Db<KV> {
+    kv: KV,
+    // Per-home notification handles used to wake `wait_for_leaf` callers as
+    // soon as a leaf is stored, instead of having them poll.
+    leaf_notifies: Arc<Mutex<HashMap<String, Arc<Notify>>>>,
+    // When set, every stored value is encrypted at rest with this key; keys
+    // stay plaintext so column family iteration keeps working.
+    encryption_key: Option<EncryptionKey>,
+}
+
+/// The production `DB`, backed by RocksDB. Agent code should use this; the
+/// `Db<KV>` generic is only needed by callers swapping in another
+/// `KvStore` (e.g. `kv::MemoryKv` in tests).
+pub type DB = Db<RocksKv>;
 
 impl From<Rocks> for DB {
     fn from(rocks: Rocks) -> Self {
-        Self(Arc::new(rocks))
+        Self {
+            kv: RocksKv::from(rocks),
+            leaf_notifies: Default::default(),
+            encryption_key: None,
+        }
     }
 }
 
@@ -46,14 +110,47 @@ pub enum DbError {
     /// Optics Error
     #[error("{0}")]
     OpticsError(#[from] OpticsError),
+    /// Failed to decrypt a value read from the DB
+    #[error("failed to decrypt value at rest (wrong key, or the value or key was tampered with)")]
+    DecryptionError,
+    /// Tried to read or write a column family that wasn't opened
+    #[error("column family {0} is not open on this db")]
+    MissingColumnFamily(String),
+    /// A legacy-format key matched a migration prefix more than once, or
+    /// matched more than one prefix, so which occurrence delimits
+    /// `home_name` from `key` is ambiguous
+    #[error("legacy key {0:?} has an ambiguous migration boundary")]
+    AmbiguousLegacyKey(Vec<u8>),
 }
 
 type Result<T> = std::result::Result<T, DbError>;
 
 impl DB {
-    /// Opens db at `db_path` and creates if missing
+    /// Opens db at `db_path`, creating it (and its column families) if
+    /// missing, and migrates any legacy prefixed keys left over from before
+    /// column families existed.
     #[tracing::instrument(err)]
     pub fn from_path(db_path: &str) -> color_eyre::Result<DB> {
+        Self::from_path_inner(db_path, None)
+    }
+
+    /// Like `from_path`, but encrypts every stored value at rest with `key`.
+    /// Useful for operators running validators/relayers on shared or cloud
+    /// hosts.
+    ///
+    /// The key is set *before* legacy keys are migrated, so legacy values
+    /// are unsealed under this key (with their legacy prefixed key as
+    /// associated data) and resealed under their new keys. A legacy value
+    /// that isn't sealed under this key, such as a plaintext one, fails
+    /// migration with `DbError::DecryptionError` and the open fails.
+    pub fn from_path_encrypted(db_path: &str, key: [u8; 32]) -> color_eyre::Result<DB> {
+        Self::from_path_inner(db_path, Some(EncryptionKey::new(key)))
+    }
+
+    fn from_path_inner(
+        db_path: &str,
+        encryption_key: Option<EncryptionKey>,
+    ) -> color_eyre::Result<DB> {
         // Canonicalize ensures existence, so we have to do that, then extend
         let mut path = Path::new(".").canonicalize()?;
         path.extend(&[db_path]);
@@ -68,74 +165,231 @@ impl DB {
 
         let mut opts = Options::default();
         opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+
+        let cf_descriptors = std::iter::once(rocksdb::DEFAULT_COLUMN_FAMILY_NAME)
+            .chain(RECORD_COLUMN_FAMILIES.iter().copied())
+            .map(|name| ColumnFamilyDescriptor::new(name, Options::default()));
 
-        Rocks::open(&opts, &path)
+        let mut db: DB = Rocks::open_cf_descriptors(&opts, &path, cf_descriptors)
             .wrap_err(format!(
                 "Failed to open db path {}, canonicalized as {:?}",
                 db_path, path
-            ))
-            .map(Into::into)
+            ))?
+            .into();
+        db.encryption_key = encryption_key;
+
+        db.migrate_legacy_keys()
+            .wrap_err("Failed to migrate legacy prefixed keys into column families")?;
+
+        Ok(db)
     }
 
+    /// One-time migration: earlier versions of this DB kept every record in
+    /// the default column family, distinguished only by a string prefix
+    /// embedded in the key. Column families now do that separation, so on
+    /// every open we scan the default CF once, split any legacy-format key
+    /// back into `<home_name><key>`, and reinsert it into the CF its prefix
+    /// maps to. A database with nothing left under a legacy prefix (i.e.
+    /// every previous open has already migrated it) does no writes here.
+    fn migrate_legacy_keys(&self) -> Result<()> {
+        let mut batch = Batch::default();
+        let mut migrated = 0usize;
+
+        for (key, value) in self.kv.kv_iter(rocksdb::DEFAULT_COLUMN_FAMILY_NAME)? {
+            let Some((cf, home_and_key)) = split_legacy_key(&key)? else {
+                continue;
+            };
+            // The value was sealed (if encryption is enabled) using the
+            // legacy `<home_name><type_prefix><key>` as associated data;
+            // unseal with that, then reseal under the new `<home_name><key>`
+            // so the migrated value authenticates under the new scheme.
+            let plain = self.unseal(&key, value.into_vec())?;
+            let sealed = self.seal(&home_and_key, plain);
+            batch.put(cf, home_and_key, sealed);
+            batch.delete(rocksdb::DEFAULT_COLUMN_FAMILY_NAME, key.into_vec());
+            migrated += 1;
+        }
+
+        if migrated > 0 {
+            info!(
+                migrated,
+                "migrated legacy prefixed keys into column families"
+            );
+            self.kv.kv_write_batch(batch)?;
+        }
+        Ok(())
+    }
+}
+
+/// If `key` contains one of the legacy `<home_name><type_prefix><key>`
+/// prefixes, return the column family it now belongs to and the
+/// `<home_name><key>` it should be stored under.
+///
+/// The type-prefix match isn't anchored to a known home-name boundary (we
+/// don't have a list of home names to anchor against), so as a safety net
+/// this errors instead of guessing if the key matches a prefix more than
+/// once, or matches more than one distinct prefix: either would mean we
+/// can't tell which occurrence is the real delimiter, and slicing at the
+/// wrong one would silently corrupt a migrated record.
+fn split_legacy_key(key: &[u8]) -> Result<Option<(&'static str, Vec<u8>)>> {
+    let mut found: Option<(usize, usize, &'static str)> = None;
+
+    for (legacy_prefix, cf) in LEGACY_PREFIXES {
+        let needle = legacy_prefix.as_bytes();
+        let mut positions = find_subslices(key, needle);
+        match (positions.next(), positions.next()) {
+            (None, _) => continue,
+            (Some(_), Some(_)) => return Err(DbError::AmbiguousLegacyKey(key.to_vec())),
+            (Some(idx), None) => {
+                if found.is_some() {
+                    return Err(DbError::AmbiguousLegacyKey(key.to_vec()));
+                }
+                found = Some((idx, needle.len(), cf));
+            }
+        }
+    }
+
+    Ok(found.map(|(idx, needle_len, cf)| {
+        let mut home_and_key = Vec::with_capacity(key.len() - needle_len);
+        home_and_key.extend_from_slice(&key[..idx]);
+        home_and_key.extend_from_slice(&key[idx + needle_len..]);
+        (cf, home_and_key)
+    }))
+}
+
+fn find_subslices<'k>(haystack: &'k [u8], needle: &[u8]) -> impl Iterator<Item = usize> + 'k {
+    let needle_len = needle.len();
+    let needle = needle.to_vec();
+    haystack
+        .windows(needle_len)
+        .enumerate()
+        .filter_map(move |(idx, w)| (w == needle.as_slice()).then_some(idx))
+}
+
+impl<KV: KvStore> Db<KV> {
     /// Store a value in the DB
-    fn _store(&self, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Result<()> {
-        Ok(self.0.put(key, value)?)
+    fn _store(&self, cf: &str, key: impl AsRef<[u8]>, value: impl AsRef<[u8]>) -> Result<()> {
+        self.kv.kv_put(cf, key.as_ref(), value.as_ref())
     }
 
     /// Retrieve a value from the DB
-    fn _retrieve(&self, key: impl AsRef<[u8]>) -> Result<Option<Vec<u8>>> {
-        Ok(self.0.get(key)?)
+    fn _retrieve(&self, cf: &str, key: impl AsRef<[u8]>) -> Result<Option<Vec<u8>>> {
+        self.kv.kv_get(cf, key.as_ref())
+    }
+
+    /// Fetch (creating if necessary) the `Notify` handle used to wake
+    /// `wait_for_leaf` callers for this home.
+    fn leaf_notify(&self, home_name: impl AsRef<[u8]>) -> Arc<Notify> {
+        let key = String::from_utf8_lossy(home_name.as_ref()).into_owned();
+        self.leaf_notifies
+            .lock()
+            .expect("leaf notify lock poisoned")
+            .entry(key)
+            .or_insert_with(|| Arc::new(Notify::new()))
+            .clone()
+    }
+
+    /// Build the full `<home_name><key>` byte string for an entry within a CF
+    fn home_key(home_name: impl AsRef<[u8]>, key: impl AsRef<[u8]>) -> Vec<u8> {
+        let mut buf = vec![];
+        buf.extend(home_name.as_ref());
+        buf.extend(key.as_ref());
+        buf
+    }
+
+    /// Encrypt `value` for storage under `full_key`, if an encryption key is
+    /// configured; otherwise pass it through unchanged.
+    fn seal(&self, full_key: &[u8], value: impl AsRef<[u8]>) -> Vec<u8> {
+        match &self.encryption_key {
+            Some(enc) => enc.encrypt(full_key, value.as_ref()),
+            None => value.as_ref().to_vec(),
+        }
+    }
+
+    /// Decrypt a value read from storage under `full_key`, if an encryption
+    /// key is configured; otherwise pass it through unchanged.
+    fn unseal(&self, full_key: &[u8], value: Vec<u8>) -> Result<Vec<u8>> {
+        match &self.encryption_key {
+            Some(enc) => enc.decrypt(full_key, &value),
+            None => Ok(value),
+        }
     }
 
-    /// Prefix a key and store in the DB
+    /// Store a value for `key` in `cf`
     fn prefix_store(
         &self,
         home_name: impl AsRef<[u8]>,
-        prefix: impl AsRef<[u8]>,
+        cf: &str,
         key: impl AsRef<[u8]>,
         value: impl AsRef<[u8]>,
     ) -> Result<()> {
-        let mut buf = vec![];
-        buf.extend(home_name.as_ref());
-        buf.extend(prefix.as_ref());
-        buf.extend(key.as_ref());
-        self._store(buf, value)
+        let full_key = Self::home_key(home_name, key);
+        let sealed = self.seal(&full_key, value);
+        self._store(cf, full_key, sealed)
     }
 
-    /// Prefix the key and retrieve
+    /// Retrieve the value for `key` in `cf`
     fn prefix_retrieve(
         &self,
         home_name: impl AsRef<[u8]>,
-        prefix: impl AsRef<[u8]>,
+        cf: &str,
         key: impl AsRef<[u8]>,
     ) -> Result<Option<Vec<u8>>> {
-        let mut buf = vec![];
-        buf.extend(home_name.as_ref());
-        buf.extend(prefix.as_ref());
-        buf.extend(key.as_ref());
-        self._retrieve(buf)
+        let full_key = Self::home_key(home_name, key);
+        self._retrieve(cf, &full_key)?
+            .map(|value| self.unseal(&full_key, value))
+            .transpose()
+    }
+
+    /// Queue a key/value pair onto `batch`, without committing it. Callers
+    /// commit the batch themselves, once, so that every key queued onto it
+    /// lands atomically.
+    fn prefix_store_batch(
+        &self,
+        batch: &mut Batch,
+        home_name: impl AsRef<[u8]>,
+        cf: &'static str,
+        key: impl AsRef<[u8]>,
+        value: impl AsRef<[u8]>,
+    ) {
+        let full_key = Self::home_key(home_name, key);
+        let sealed = self.seal(&full_key, value);
+        batch.put(cf, full_key, sealed);
     }
 
     /// Store any encodeable
     pub fn store_encodable<V: Encode>(
         &self,
         home_name: impl AsRef<[u8]>,
-        prefix: impl AsRef<[u8]>,
+        cf: &str,
         key: impl AsRef<[u8]>,
         value: &V,
     ) -> Result<()> {
-        self.prefix_store(home_name, prefix, key, value.to_vec())
+        self.prefix_store(home_name, cf, key, value.to_vec())
+    }
+
+    /// Queue an encodeable onto `batch`
+    fn store_encodable_batch<V: Encode>(
+        &self,
+        batch: &mut Batch,
+        home_name: impl AsRef<[u8]>,
+        cf: &'static str,
+        key: impl AsRef<[u8]>,
+        value: &V,
+    ) {
+        self.prefix_store_batch(batch, home_name, cf, key, value.to_vec())
     }
 
     /// Retrieve and attempt to decode
     pub fn retrieve_decodable<V: Decode>(
         &self,
         home_name: impl AsRef<[u8]>,
-        prefix: impl AsRef<[u8]>,
+        cf: &str,
         key: impl AsRef<[u8]>,
     ) -> Result<Option<V>> {
         Ok(self
-            .prefix_retrieve(home_name, prefix, key)?
+            .prefix_retrieve(home_name, cf, key)?
             .map(|val| V::read_from(&mut val.as_slice()))
             .transpose()?)
     }
@@ -144,21 +398,33 @@ impl DB {
     pub fn store_keyed_encodable<K: Encode, V: Encode>(
         &self,
         home_name: impl AsRef<[u8]>,
-        prefix: impl AsRef<[u8]>,
+        cf: &str,
         key: &K,
         value: &V,
     ) -> Result<()> {
-        self.store_encodable(home_name, prefix, key.to_vec(), value)
+        self.store_encodable(home_name, cf, key.to_vec(), value)
+    }
+
+    /// Queue a keyed encodeable onto `batch`
+    fn store_keyed_encodable_batch<K: Encode, V: Encode>(
+        &self,
+        batch: &mut Batch,
+        home_name: impl AsRef<[u8]>,
+        cf: &'static str,
+        key: &K,
+        value: &V,
+    ) {
+        self.store_encodable_batch(batch, home_name, cf, key.to_vec(), value)
     }
 
     /// Retrieve any decodable
     pub fn retrieve_keyed_decodable<K: Encode, V: Decode>(
         &self,
         home_name: impl AsRef<[u8]>,
-        prefix: impl AsRef<[u8]>,
+        cf: &str,
         key: &K,
     ) -> Result<Option<V>> {
-        self.retrieve_decodable(home_name, prefix, key.to_vec())
+        self.retrieve_decodable(home_name, cf, key.to_vec())
     }
 
     /// Store a raw committed message
@@ -181,13 +447,22 @@ impl DB {
             leaf_index = message.leaf_index,
             "storing raw committed message in db"
         );
-        self.store_keyed_encodable(&home_name, LEAF_HASH, &leaf_hash, message)?;
-        self.store_leaf(
+
+        // Queue every key this message touches onto one batch so they commit
+        // atomically: a crash or error partway through used to be able to
+        // leave e.g. a leaf indexed by hash but not reachable by index.
+        let mut batch = Batch::default();
+        self.store_keyed_encodable_batch(&mut batch, &home_name, LEAF_HASH, &leaf_hash, message);
+        self.queue_leaf(
+            &mut batch,
             &home_name,
             message.leaf_index,
             destination_and_nonce,
             leaf_hash,
         )?;
+        self.kv.kv_write_batch(batch)?;
+
+        self.leaf_notify(&home_name).notify_waiters();
         Ok(())
     }
 
@@ -197,17 +472,34 @@ impl DB {
         home_name: impl AsRef<[u8]>,
         leaf_index: u32,
     ) -> Result<()> {
+        let mut batch = Batch::default();
+        if self.queue_latest_leaf_index(&mut batch, &home_name, leaf_index)? {
+            self.kv.kv_write_batch(batch)?;
+            self.leaf_notify(&home_name).notify_waiters();
+        }
+        Ok(())
+    }
+
+    /// Queue the `LATEST_LEAF` write onto `batch` if `leaf_index` advances it,
+    /// returning whether a write was queued.
+    fn queue_latest_leaf_index(
+        &self,
+        batch: &mut Batch,
+        home_name: impl AsRef<[u8]>,
+        leaf_index: u32,
+    ) -> Result<bool> {
         if let Ok(Some(idx)) = self.retrieve_latest_leaf_index(&home_name) {
             if leaf_index <= idx {
-                return Ok(());
+                return Ok(false);
             }
         }
-        self.store_encodable(&home_name, "", LATEST_LEAF, &leaf_index)
+        self.store_encodable_batch(batch, &home_name, LATEST_LEAF, "", &leaf_index);
+        Ok(true)
     }
 
     /// Retrieve the highest known leaf_index
     pub fn retrieve_latest_leaf_index(&self, home_name: impl AsRef<[u8]>) -> Result<Option<u32>> {
-        self.retrieve_decodable(home_name, "", LATEST_LEAF)
+        self.retrieve_decodable(home_name, LATEST_LEAF, "")
     }
 
     /// Store the leaf_hash keyed by leaf_index
@@ -223,9 +515,42 @@ impl DB {
             leaf_hash = ?leaf_hash,
             "storing leaf hash keyed by index and dest+nonce"
         );
-        self.store_keyed_encodable(&home_name, NONCE, &destination_and_nonce, &leaf_hash)?;
-        self.store_keyed_encodable(&home_name, LEAF_IDX, &leaf_index, &leaf_hash)?;
-        self.update_latest_leaf_index(&home_name, leaf_index)
+        let mut batch = Batch::default();
+        self.queue_leaf(
+            &mut batch,
+            &home_name,
+            leaf_index,
+            destination_and_nonce,
+            leaf_hash,
+        )?;
+        self.kv.kv_write_batch(batch)?;
+        // Wake any `wait_for_leaf` callers waiting on this leaf_index, even if
+        // it isn't the latest leaf (e.g. leaves arriving out of order).
+        self.leaf_notify(&home_name).notify_waiters();
+        Ok(())
+    }
+
+    /// Queue the `NONCE`, `LEAF_IDX`, and (if it advances) `LATEST_LEAF`
+    /// writes for a leaf onto `batch`, so a caller can commit them alongside
+    /// other keys in one atomic write.
+    fn queue_leaf(
+        &self,
+        batch: &mut Batch,
+        home_name: impl AsRef<[u8]>,
+        leaf_index: u32,
+        destination_and_nonce: u64,
+        leaf_hash: H256,
+    ) -> Result<()> {
+        self.store_keyed_encodable_batch(
+            batch,
+            &home_name,
+            NONCE,
+            &destination_and_nonce,
+            &leaf_hash,
+        );
+        self.store_keyed_encodable_batch(batch, &home_name, LEAF_IDX, &leaf_index, &leaf_hash);
+        self.queue_latest_leaf_index(batch, &home_name, leaf_index)?;
+        Ok(())
     }
 
     /// Retrieve a raw committed message by its leaf hash
@@ -286,12 +611,7 @@ impl DB {
 
     /// Retrieve the latest committed
     pub fn retrieve_latest_root(&self, home_name: impl AsRef<[u8]>) -> Result<Option<H256>> {
-        self.retrieve_decodable(home_name, "", LATEST_ROOT)
-    }
-
-    fn store_latest_root(&self, home_name: impl AsRef<[u8]>, root: H256) -> Result<()> {
-        debug!(root = ?root, "storing new latest root in DB");
-        self.store_encodable(home_name, "", LATEST_ROOT, &root)
+        self.retrieve_decodable(home_name, LATEST_ROOT, "")
     }
 
     /// Store a signed update
@@ -302,24 +622,51 @@ impl DB {
             "storing update in DB"
         );
 
-        // If there is no latet root, or if this update is on the latest root
-        // update latest root
+        let mut batch = Batch::default();
+
+        // If there is no latest root, or if this update is on the latest
+        // root, update latest root
         match self.retrieve_latest_root(&home_name)? {
             Some(root) => {
                 if root == update.update.previous_root {
-                    self.store_latest_root(&home_name, update.update.new_root)?;
+                    debug!(root = ?update.update.new_root, "storing new latest root in DB");
+                    self.store_encodable_batch(
+                        &mut batch,
+                        &home_name,
+                        LATEST_ROOT,
+                        "",
+                        &update.update.new_root,
+                    );
                 }
             }
-            None => self.store_latest_root(&home_name, update.update.new_root)?,
+            None => {
+                debug!(root = ?update.update.new_root, "storing new latest root in DB");
+                self.store_encodable_batch(
+                    &mut batch,
+                    &home_name,
+                    LATEST_ROOT,
+                    "",
+                    &update.update.new_root,
+                );
+            }
         }
 
-        self.store_keyed_encodable(&home_name, PREV_ROOT, &update.update.previous_root, update)?;
-        self.store_keyed_encodable(
+        self.store_keyed_encodable_batch(
+            &mut batch,
+            &home_name,
+            PREV_ROOT,
+            &update.update.previous_root,
+            update,
+        );
+        self.store_keyed_encodable_batch(
+            &mut batch,
             &home_name,
             NEW_ROOT,
             &update.update.new_root,
             &update.update.previous_root,
-        )
+        );
+
+        self.kv.kv_write_batch(batch)
     }
 
     /// Retrieve an update by its previous root
@@ -346,9 +693,19 @@ impl DB {
         }
     }
 
-    /// Iterate over all leaves
-    pub fn leaf_iterator(&self) -> PrefixIterator<H256> {
-        PrefixIterator::new(self.0.prefix_iterator(LEAF_IDX), LEAF_IDX.as_ref())
+    /// Iterate over all leaves, reading the leaf index column family
+    /// directly instead of filtering the whole keyspace by prefix. Errors if
+    /// the leaf index CF isn't open on this db.
+    pub fn leaf_iterator(&self) -> Result<CfIterator<'_, H256>> {
+        let encryption_key = self.encryption_key.clone();
+        let iter = self.kv.kv_iter(LEAF_IDX)?.filter_map(move |(key, value)| {
+            let value = match &encryption_key {
+                Some(enc) => enc.decrypt(&key, &value).ok()?,
+                None => Vec::from(value),
+            };
+            Some((key, value.into_boxed_slice()))
+        });
+        Ok(CfIterator::new(iter))
     }
 
     /// Store a proof by its leaf index
@@ -371,8 +728,11 @@ impl DB {
         self.retrieve_keyed_decodable(home_name, PROOF, &leaf_index)
     }
 
-    // TODO(james): this is a quick-fix for the prover_sync and I don't like it
-    /// poll db ever 100 milliseconds waitinf for a leaf.
+    /// Wait for a leaf to appear at `leaf_index`, without polling.
+    ///
+    /// Registers interest in the home's `Notify` *before* checking the DB, so
+    /// a leaf stored concurrently between the check and the await can't be
+    /// missed (the notification is already pending when that happens).
     pub fn wait_for_leaf(
         &self,
         home_name: impl AsRef<[u8]>,
@@ -381,11 +741,94 @@ impl DB {
         let slf = self.clone();
         async move {
             loop {
+                let notify = slf.leaf_notify(&home_name);
+                let notified = notify.notified();
                 if let Some(leaf) = slf.leaf_by_leaf_index(&home_name, leaf_index)? {
                     return Ok(Some(leaf));
                 }
-                sleep(Duration::from_millis(100)).await
+                notified.await;
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use kv::MemoryKv;
+
+    fn memory_db() -> Db<MemoryKv> {
+        Db {
+            kv: MemoryKv::default(),
+            leaf_notifies: Default::default(),
+            encryption_key: None,
+        }
+    }
+
+    fn encrypted_memory_db(key: [u8; 32]) -> Db<MemoryKv> {
+        Db {
+            encryption_key: Some(EncryptionKey::new(key)),
+            ..memory_db()
+        }
+    }
+
+    #[test]
+    fn store_and_retrieve_roundtrips_through_encryption() {
+        let db = encrypted_memory_db([7u8; 32]);
+        db.store_encodable(b"home", LATEST_LEAF, "", &42u32).unwrap();
+        let got: Option<u32> = db.retrieve_decodable(b"home", LATEST_LEAF, "").unwrap();
+        assert_eq!(got, Some(42));
+    }
+
+    #[test]
+    fn decrypting_with_the_wrong_key_fails() {
+        let db = encrypted_memory_db([1u8; 32]);
+        db.store_encodable(b"home", LATEST_LEAF, "", &42u32).unwrap();
+
+        // Shares the same underlying `MemoryKv` storage, but reads it back
+        // under a different key than it was sealed with.
+        let wrong_key_db = Db {
+            encryption_key: Some(EncryptionKey::new([2u8; 32])),
+            ..db.clone()
+        };
+        let err = wrong_key_db
+            .retrieve_decodable::<u32>(b"home", LATEST_LEAF, "")
+            .unwrap_err();
+        assert!(matches!(err, DbError::DecryptionError));
+    }
+
+    #[test]
+    fn sealed_value_does_not_decrypt_under_a_different_aad() {
+        // `seal`/`unseal` authenticate the full `<home_name><key>` as
+        // associated data so a value can't be relocated onto a different
+        // key; a value sealed under one full key must fail to decrypt under
+        // another, even with the correct encryption key.
+        let key = EncryptionKey::new([3u8; 32]);
+        let sealed = key.encrypt(b"home_leaf_index_one", b"plaintext");
+        let err = key.decrypt(b"home_leaf_index_two", &sealed).unwrap_err();
+        assert!(matches!(err, DbError::DecryptionError));
+    }
+
+    #[tokio::test]
+    async fn wait_for_leaf_wakes_on_concurrent_store() {
+        let db = memory_db();
+        let waiter = db.clone();
+        let handle = tokio::spawn(async move { waiter.wait_for_leaf(b"home", 0).await });
+
+        // Give the waiter a chance to register interest in the `Notify`
+        // *before* the leaf is stored: the whole point of creating the
+        // `Notified` future ahead of the DB check (the lost-wakeup fix from
+        // chunk0-1) is that a leaf arriving in this window still wakes it.
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        db.store_leaf(b"home", 0, 0, H256::repeat_byte(9)).unwrap();
+
+        let leaf = tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .expect("wait_for_leaf did not wake up after store_leaf")
+            .unwrap()
+            .unwrap();
+        assert_eq!(leaf, Some(H256::repeat_byte(9)));
+    }
+}